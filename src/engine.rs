@@ -2,60 +2,149 @@
 
 use futures::future::{join_all, ok};
 use futures::prelude::*;
+use futures::{Async, Poll};
 use errors::*;
 use std::net::IpAddr;
 use std::cell::RefCell;
-use std::io;
 use std::rc::Rc;
+use std::time::Duration;
 use tokio_core::reactor;
 use tokio_ping::{self, Pinger};
 
-/// Number of ping attempts before giving up
-const MAX_PER_TARGET: u64 = 5;
-
 pub type Times = Vec<Option<f64>>;
 
+/// Every RTT sample observed per target, in send order; `None` marks an unanswered probe.
+pub type Samples = Vec<Vec<Option<f64>>>;
+
+/// Paces an inner ping stream so that, after its first item, successive items are not polled
+/// again until `interval` has elapsed. Built on `reactor::Timeout` (also used for the overall
+/// deadline in `measure` below) rather than on any `tokio_ping` pacing hook, since `PingChain` exposes no
+/// verified API for that.
+struct Paced<S> {
+    inner: S,
+    interval: Duration,
+    handle: reactor::Handle,
+    delay: Option<reactor::Timeout>,
+}
+
+impl<S> Paced<S> {
+    fn new(inner: S, interval: Duration, handle: reactor::Handle) -> Self {
+        Paced {
+            inner,
+            interval,
+            handle,
+            delay: None,
+        }
+    }
+}
+
+impl<S> Stream for Paced<S>
+where
+    S: Stream<Item = Option<f64>, Error = tokio_ping::Error>,
+{
+    type Item = Option<f64>;
+    type Error = tokio_ping::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Some(delay) = self.delay.as_mut() {
+            match delay.poll() {
+                Ok(Async::Ready(())) => self.delay = None,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                // The reactor is going away; let the inner stream settle the matter.
+                Err(_) => self.delay = None,
+            }
+        }
+        match self.inner.poll()? {
+            Async::Ready(item) => {
+                if item.is_some() {
+                    self.delay = Some(
+                        reactor::Timeout::new(self.interval, &self.handle)
+                            .expect("cannot set up per-target pacing timer"),
+                    );
+                }
+                Ok(Async::Ready(item))
+            }
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
 /// Collects ping times for each target. Ping is stopped either if the best time is below `cutoff`
-/// or if `MAX_PER_TARGET` attempts are taken.
-fn measure<S>(reactor: &mut reactor::Core, targets: Vec<S>, cutoff: f64) -> Result<Times>
+/// or if `count` attempts are taken. Returns the best RTT per target alongside every raw sample
+/// (including unanswered probes), so callers can derive loss and jitter statistics.
+///
+/// The whole collection is bounded by `timeout`: once it elapses, whatever has been gathered so
+/// far is returned, with targets that never answered left as `None`. This guarantees `measure`
+/// cannot block the reactor longer than `timeout`, even if a target silently drops every probe.
+fn measure<S>(
+    reactor: &mut reactor::Core,
+    targets: Vec<S>,
+    cutoff: f64,
+    count: u64,
+    timeout: Duration,
+) -> Result<(Times, Samples)>
 where
     S: Stream<Item = Option<f64>, Error = tokio_ping::Error> + 'static,
 {
     let best: Rc<RefCell<Times>> = Rc::new(RefCell::new(vec![None; targets.len()]));
+    let samples: Rc<RefCell<Samples>> = Rc::new(RefCell::new(vec![Vec::new(); targets.len()]));
     {
         let f = targets.into_iter().enumerate().map(|(i, target)| {
             let best = best.clone();
+            let samples = samples.clone();
             target
-                .take(MAX_PER_TARGET)
-                .filter_map(|elt| elt) // take out None values
+                .take(count)
                 .take_while(move |elt| {
+                    samples.borrow_mut()[i].push(*elt);
                     let mut best = best.borrow_mut();
-                    match best[i] {
-                        Some(b) if b > *elt => best[i] = Some(*elt),
-                        None                => best[i] = Some(*elt),
+                    match (best[i], *elt) {
+                        (Some(b), Some(elt)) if b > elt => best[i] = Some(elt),
+                        (None, Some(elt))                => best[i] = Some(elt),
                         _ => (),
                     };
-                    ok(*elt >= cutoff)
+                    ok(elt.map(|elt| elt >= cutoff).unwrap_or(true))
                 })
                 .for_each(|_| Ok(()))
         });
-        reactor.run(join_all(f))?;
+        let work = join_all(f).map(|_| false).map_err(Error::from);
+        let deadline = reactor::Timeout::new(timeout, &reactor.handle())
+            .chain_err(|| "cannot set up overall timeout")?
+            .map(|_| true)
+            .map_err(Error::from);
+        reactor.run(work.select(deadline)).map_err(|(e, _)| e)?;
     }
-    Ok(Rc::try_unwrap(best).unwrap().into_inner())
+    Ok((
+        Rc::try_unwrap(best).unwrap().into_inner(),
+        Rc::try_unwrap(samples).unwrap().into_inner(),
+    ))
 }
 
 /// Sets up async core and starts parallel ping.
-pub fn ping_all<'a, I>(targets: I, cutoff: f64) -> Result<Times>
+///
+/// `count` bounds the number of echo requests sent per target and `timeout` bounds the overall
+/// wall-clock runtime so a single unreachable target cannot stall the whole check; both are
+/// applied in `measure`. `interval` paces successive requests to the same target instead of
+/// firing them back-to-back, via the `Paced` stream wrapper above.
+pub fn ping_all<'a, I>(
+    targets: I,
+    cutoff: f64,
+    count: u64,
+    interval: Duration,
+    timeout: Duration,
+) -> Result<(Times, Samples)>
 where
     I: Iterator<Item = &'a IpAddr>,
 {
     let mut reactor = reactor::Core::new().unwrap();
     let hdl = reactor.handle();
-    let streams = targets
-        .map(|addr| Pinger::new(&hdl).map(|p| p.chain(*addr).stream()))
-        .collect::<io::Result<_>>()
-        .chain_err(|| "cannot create ICMP socket - missing privileges?".to_string())?;
-    measure(&mut reactor, streams, cutoff)
+    let mut streams = Vec::new();
+    for addr in targets {
+        let stream = Pinger::new(&hdl)
+            .map(|p| p.chain(*addr).stream())
+            .chain_err(|| "cannot create ICMP socket - missing privileges?".to_string())?;
+        streams.push(Paced::new(stream, interval, hdl.clone()));
+    }
+    measure(&mut reactor, streams, cutoff, count, timeout)
 }
 
 #[cfg(test)]
@@ -65,6 +154,12 @@ mod tests {
     use futures::stream;
     use std::vec::IntoIter;
 
+    const COUNT: u64 = 5;
+
+    fn timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
     fn fake_stream<'a>(
         ping_times: &'a [&'a [Option<f64>]],
     ) -> Vec<stream::IterOk<IntoIter<Option<f64>>, tokio_ping::Error>> {
@@ -84,7 +179,7 @@ mod tests {
     fn test_singletons() {
         let times = fake_stream(&[&[Some(54.1)], &[Some(0.2)]]);
         assert_eq!(
-            measure(&mut r(), times, 1e2).unwrap(),
+            measure(&mut r(), times, 1e2, COUNT, timeout()).unwrap().0,
             vec![Some(54.1), Some(0.2)]
         )
     }
@@ -102,24 +197,30 @@ mod tests {
             &[None, None, None],
         ]);
         assert_eq!(
-            measure(&mut r(), times, 1e-2).unwrap(),
+            measure(&mut r(), times, 1e-2, COUNT, timeout()).unwrap().0,
             vec![Some(53.0), Some(0.2), Some(1.0), None]
         )
     }
 
     #[test]
     fn test_max_attempts() {
-        let mut rtt = vec![Some(4.0); MAX_PER_TARGET as usize];
+        let mut rtt = vec![Some(4.0); COUNT as usize];
         rtt.push(Some(2.0));
         let times = fake_stream(&[&rtt]);
-        assert_eq!(measure(&mut r(), times, 1e2).unwrap(), vec![Some(4.0)]);
+        assert_eq!(
+            measure(&mut r(), times, 1e2, COUNT, timeout()).unwrap().0,
+            vec![Some(4.0)]
+        );
     }
 
     #[test]
     fn test_stop_single_target_below_cutoff() {
         // should pick 3rd one (first below cutoff
         let times = fake_stream(&[&[Some(9.0), Some(8.0), Some(7.0), Some(6.0)]]);
-        assert_eq!(measure(&mut r(), times, 8.0).unwrap(), vec![Some(7.0)]);
+        assert_eq!(
+            measure(&mut r(), times, 8.0, COUNT, timeout()).unwrap().0,
+            vec![Some(7.0)]
+        );
     }
 
     #[test]
@@ -132,9 +233,37 @@ mod tests {
             &[Some(9.0), Some(8.0), Some(7.0), Some(6.0)],
         ]);
         assert_eq!(
-            measure(&mut r(), times, 5.1).unwrap(),
+            measure(&mut r(), times, 5.1, COUNT, timeout()).unwrap().0,
             vec![Some(5.0), Some(5.0), Some(6.0)]
         );
     }
 
+    #[test]
+    fn test_samples_retain_losses() {
+        let times = fake_stream(&[&[Some(9.0), None, Some(7.0)]]);
+        let (best, samples) = measure(&mut r(), times, 1e-2, COUNT, timeout()).unwrap();
+        assert_eq!(best, vec![Some(7.0)]);
+        assert_eq!(samples, vec![vec![Some(9.0), None, Some(7.0)]]);
+    }
+
+    /// A stream that never resolves, standing in for a target whose probes are silently dropped.
+    struct Never;
+
+    impl Stream for Never {
+        type Item = Option<f64>;
+        type Error = tokio_ping::Error;
+
+        fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+            Ok(Async::NotReady)
+        }
+    }
+
+    #[test]
+    fn test_overall_timeout_yields_partial_results() {
+        let fast = stream::iter_ok::<_, tokio_ping::Error>(vec![Some(1.0)].into_iter());
+        let streams: Vec<Box<Stream<Item = Option<f64>, Error = tokio_ping::Error>>> =
+            vec![Box::new(fast), Box::new(Never)];
+        let (best, _) = measure(&mut r(), streams, 1e-2, COUNT, Duration::from_millis(50)).unwrap();
+        assert_eq!(best, vec![Some(1.0), None]);
+    }
 }
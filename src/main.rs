@@ -25,19 +25,25 @@ mod errors {
     }
 }
 
-use engine::{ping_all, Times};
+use engine::{ping_all, Samples, Times};
 use error_chain::ChainedError;
 use errors::*;
 use status::Status;
 use std::fmt::Write;
 use std::net::{IpAddr, ToSocketAddrs};
 use std::process;
+use std::time::Duration;
 
 /// Transparent AF filter
 fn is_any(_: &IpAddr) -> bool {
     true
 }
 
+/// Converts a fractional number of seconds, as accepted on the command line, to a `Duration`.
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9).round() as u32)
+}
+
 /// List of ping target addresses
 ///
 /// We keep a reference to the original command line argument for output. If a numeric target was
@@ -77,11 +83,19 @@ impl<'a> Targets<'a> {
     }
 
     /// Actually invokes the ping machinery and feeds results to the next stage.
-    fn ping(self, cutoff: f64) -> Result<PingTimes<'a>> {
-        let times = ping_all(self.addr.iter(), cutoff)?;
+    fn ping(
+        self,
+        cutoff: f64,
+        count: u64,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Result<PingTimes<'a>> {
+        let (times, samples) = ping_all(self.addr.iter(), cutoff, count, interval, timeout)?;
         Ok(PingTimes {
             targets: self,
             times,
+            samples,
+            count,
         })
     }
 }
@@ -91,6 +105,64 @@ impl<'a> Targets<'a> {
 struct PingTimes<'a> {
     targets: Targets<'a>,
     times: Times,
+    samples: Samples,
+    /// Number of echo requests that were supposed to be sent per target (the `--count` value),
+    /// used as the loss denominator for a target that never got a single probe in.
+    count: u64,
+}
+
+/// Per-target summary statistics derived from the raw RTT samples, as reported by `ping`.
+struct Stats {
+    loss_pct: f64,
+    min: Option<f64>,
+    avg: Option<f64>,
+    max: Option<f64>,
+    mdev: Option<f64>,
+}
+
+impl Stats {
+    /// Computes loss, min/avg/max and mdev from one target's raw samples. As in `ping`, mdev is
+    /// the standard deviation `sqrt(mean(rtt²) - mean(rtt)²)` of the received RTTs.
+    ///
+    /// `count` is the configured number of probes per target; it is used as the loss
+    /// denominator when `samples` is empty (the target never got a single probe answered or
+    /// attempted, e.g. because the overall `--timeout` expired first), so that a target which
+    /// was never even tried is reported as a full loss rather than as 0% loss.
+    fn from_samples(samples: &[Option<f64>], count: u64) -> Self {
+        let sent = if samples.is_empty() {
+            count
+        } else {
+            samples.len() as u64
+        };
+        let received: Vec<f64> = samples.iter().filter_map(|s| *s).collect();
+        let loss_pct = if sent == 0 {
+            0.
+        } else {
+            (sent - received.len() as u64) as f64 / sent as f64 * 1e2
+        };
+        if received.is_empty() {
+            return Stats {
+                loss_pct,
+                min: None,
+                avg: None,
+                max: None,
+                mdev: None,
+            };
+        }
+        let n = received.len() as f64;
+        let avg = received.iter().sum::<f64>() / n;
+        let mean_sq = received.iter().map(|v| v * v).sum::<f64>() / n;
+        // For near-constant samples, rounding can push the variance fractionally below zero;
+        // clamp it before the sqrt so that case reports 0 instead of NaN.
+        let variance = (mean_sq - avg * avg).max(0.);
+        Stats {
+            loss_pct,
+            min: Some(received.iter().cloned().fold(received[0], f64::min)),
+            avg: Some(avg),
+            max: Some(received.iter().cloned().fold(received[0], f64::max)),
+            mdev: Some(variance.sqrt()),
+        }
+    }
 }
 
 impl<'a> PingTimes<'a> {
@@ -104,17 +176,31 @@ impl<'a> PingTimes<'a> {
             .min_by(|a, b| a.partial_cmp(b).unwrap())
     }
 
-    /// Formats performance data in a Nagios-compatible way (without leading "|")
+    /// Formats performance data in a Nagios-compatible way (without leading "|"): the best RTT
+    /// per target, followed by its loss percentage and RTT min/avg/max/mdev.
     fn perfdata(&self, warn: f64, crit: f64) -> String {
-        let mut res = String::with_capacity(self.times.len() * 20);
+        let mut res = String::with_capacity(self.times.len() * 120);
         for (i, val) in self.times.iter().enumerate() {
+            let addr = self.targets.addr[i];
+            let stats = Stats::from_samples(&self.samples[i], self.count);
             write!(
                 &mut res,
-                " '{}'={:.6}s;{};{};0",
-                self.targets.addr[i],
+                " '{}'={:.6}s;{};{};0 '{}_loss'={:.1}%;;;0 '{}_rtt_min'={:.6}s;;;0 \
+                 '{}_rtt_avg'={:.6}s;;;0 '{}_rtt_max'={:.6}s;;;0 '{}_rtt_mdev'={:.6}s;;;0",
+                addr,
                 output::u(val),
                 warn,
-                crit
+                crit,
+                addr,
+                stats.loss_pct,
+                addr,
+                output::u(&stats.min),
+                addr,
+                output::u(&stats.avg),
+                addr,
+                output::u(&stats.max),
+                addr,
+                output::u(&stats.mdev),
             )
             .ok();
         }
@@ -167,6 +253,27 @@ fn run() -> Result<i32> {
                 .default_value("500")
                 .help("CRIT if no target's rtt is below"),
         )
+        .arg(
+            Arg::with_name("count")
+                .short("n")
+                .long("count")
+                .default_value("5")
+                .help("Number of echo requests to send per target"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .short("i")
+                .long("interval")
+                .default_value("1")
+                .help("Seconds to wait between echo requests to the same target"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .short("t")
+                .long("timeout")
+                .default_value("5")
+                .help("Overall deadline in seconds; targets still pending then count as losses"),
+        )
         .arg(
             Arg::with_name("ipv4")
                 .short("4")
@@ -190,6 +297,9 @@ fn run() -> Result<i32> {
 
     let warn = value_t!(args, "warn_ms", f64)? * 1e-3;
     let crit = value_t!(args, "crit_ms", f64)? * 1e-3;
+    let count = value_t!(args, "count", u64)?;
+    let interval = secs_to_duration(value_t!(args, "interval", f64)?);
+    let timeout = secs_to_duration(value_t!(args, "timeout", f64)?);
     let af_filter = match (args.is_present("ipv4"), args.is_present("ipv6")) {
         (true, false) => IpAddr::is_ipv4,
         (false, true) => IpAddr::is_ipv6,
@@ -200,7 +310,7 @@ fn run() -> Result<i32> {
             .expect("required arg HOSTS missing"),
         af_filter,
     )?
-    .ping(warn)?
+    .ping(warn, count, interval, timeout)?
     .evaluate(warn, crit);
     println!("{}: {} - {}", crate_name!(), status, output);
     Ok(status as i32)
@@ -13,6 +13,7 @@ fn pt<'a>(data: &[(&'a str, Option<f64>)]) -> PingTimes<'a> {
         t.targets.addr.push(addr(tgt));
         t.targets.host.push(tgt);
         t.times.push(time);
+        t.samples.push(vec![time]);
     }
     t
 }
@@ -70,7 +71,31 @@ fn eval_all_timeout() {
     assert_eq!(
         pt(&[("8.8.8.8", None), ("4.4.4.4", None)]).evaluate(0.3, 0.4),
         (
-            "no data | '8.8.8.8'=Us;0.3;0.4;0 '4.4.4.4'=Us;0.3;0.4;0".into(),
+            "no data | '8.8.8.8'=Us;0.3;0.4;0 '8.8.8.8_loss'=100.0%;;;0 '8.8.8.8_rtt_min'=Us;;;0 \
+             '8.8.8.8_rtt_avg'=Us;;;0 '8.8.8.8_rtt_max'=Us;;;0 '8.8.8.8_rtt_mdev'=Us;;;0 \
+             '4.4.4.4'=Us;0.3;0.4;0 '4.4.4.4_loss'=100.0%;;;0 '4.4.4.4_rtt_min'=Us;;;0 \
+             '4.4.4.4_rtt_avg'=Us;;;0 '4.4.4.4_rtt_max'=Us;;;0 '4.4.4.4_rtt_mdev'=Us;;;0"
+                .into(),
+            Critical
+        )
+    );
+}
+
+#[test]
+fn eval_never_tried_counts_as_full_loss() {
+    // Overall --timeout expired before this target's stream yielded even one sample.
+    let mut t = PingTimes::default();
+    t.targets.addr.push(addr("8.8.8.8"));
+    t.targets.host.push("8.8.8.8");
+    t.times.push(None);
+    t.samples.push(vec![]);
+    t.count = 3;
+    assert_eq!(
+        t.evaluate(0.3, 0.4),
+        (
+            "no data | '8.8.8.8'=Us;0.3;0.4;0 '8.8.8.8_loss'=100.0%;;;0 '8.8.8.8_rtt_min'=Us;;;0 \
+             '8.8.8.8_rtt_avg'=Us;;;0 '8.8.8.8_rtt_max'=Us;;;0 '8.8.8.8_rtt_mdev'=Us;;;0"
+                .into(),
             Critical
         )
     );
@@ -81,7 +106,11 @@ fn eval_ok() {
     assert_eq!(
         pt(&[("8.8.8.8", Some(0.01)), ("4.4.4.4", None)]).evaluate(0.1, 0.2),
         (
-            "best rtt 10 ms (for 8.8.8.8) | '8.8.8.8'=0.01s;0.1;0.2;0 '4.4.4.4'=Us;0.1;0.2;0"
+            "best rtt 10 ms (for 8.8.8.8) | '8.8.8.8'=0.01s;0.1;0.2;0 '8.8.8.8_loss'=0.0%;;;0 \
+             '8.8.8.8_rtt_min'=0.01s;;;0 '8.8.8.8_rtt_avg'=0.01s;;;0 '8.8.8.8_rtt_max'=0.01s;;;0 \
+             '8.8.8.8_rtt_mdev'=0s;;;0 '4.4.4.4'=Us;0.1;0.2;0 '4.4.4.4_loss'=100.0%;;;0 \
+             '4.4.4.4_rtt_min'=Us;;;0 '4.4.4.4_rtt_avg'=Us;;;0 '4.4.4.4_rtt_max'=Us;;;0 \
+             '4.4.4.4_rtt_mdev'=Us;;;0"
                 .into(),
             OK
         )
@@ -94,15 +123,38 @@ fn eval_ok_fmt_hostnames() {
     t.targets.addr.push(addr("8.8.8.8"));
     t.targets.host.push("google.ns");
     t.times.push(Some(0.054));
+    t.samples.push(vec![Some(0.054)]);
     assert_eq!(
         t.evaluate(0.1, 0.2),
         (
-            "best rtt 54 ms (for google.ns/8.8.8.8) | '8.8.8.8'=0.054s;0.1;0.2;0".into(),
+            "best rtt 54 ms (for google.ns/8.8.8.8) | '8.8.8.8'=0.054s;0.1;0.2;0 \
+             '8.8.8.8_loss'=0.0%;;;0 '8.8.8.8_rtt_min'=0.054s;;;0 '8.8.8.8_rtt_avg'=0.054s;;;0 \
+             '8.8.8.8_rtt_max'=0.054s;;;0 '8.8.8.8_rtt_mdev'=0s;;;0"
+                .into(),
             OK
         )
     );
 }
 
+#[test]
+fn eval_mdev_never_nan_for_near_constant_samples() {
+    // These four RTTs are close enough that mean(rtt^2) - mean(rtt)^2 rounds to a tiny
+    // negative f64 instead of exactly 0; mdev must still come out as 0, not NaN.
+    let mut t = PingTimes::default();
+    t.targets.addr.push(addr("8.8.8.8"));
+    t.targets.host.push("8.8.8.8");
+    t.times.push(Some(1.5221345273470037));
+    t.samples.push(vec![
+        Some(1.5221345273470037),
+        Some(1.5221345273469524),
+        Some(1.5221345273459317),
+        Some(1.5221345273469318),
+    ]);
+    let (output, _) = t.evaluate(0.1, 1.0);
+    assert!(output.contains("_rtt_mdev'=0s"));
+    assert!(!output.contains("NaN"));
+}
+
 #[test]
 fn eval_warning() {
     assert_eq!(pt(&[("8.8.8.8", Some(1.0))]).evaluate(0.1, 1.0).1, Warning);